@@ -0,0 +1,65 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+const TOKEN_LEN: usize = 32;
+const TOKEN_VALIDITY_MINUTES: i64 = 30;
+
+/// Generates a random single-use token for an email verification or
+/// password-reset link.
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+pub fn expiry() -> NaiveDateTime {
+    (Utc::now() + Duration::minutes(TOKEN_VALIDITY_MINUTES)).naive_utc()
+}
+
+fn transport() -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let host = std::env::var("SMTP_HOST")?;
+    let username = std::env::var("SMTP_USERNAME")?;
+    let password = std::env::var("SMTP_PASSWORD")?;
+    Ok(
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?
+            .credentials(Credentials::new(username, password))
+            .build(),
+    )
+}
+
+async fn send(to: &str, subject: &str, body: String) -> anyhow::Result<()> {
+    let from: Mailbox = std::env::var("MAILER_FROM_ADDRESS")?.parse()?;
+    let email = Message::builder()
+        .from(from)
+        .to(to.parse()?)
+        .subject(subject)
+        .body(body)?;
+    transport()?.send(email).await?;
+    Ok(())
+}
+
+pub async fn send_verification_email(to: &str, token: &str) -> anyhow::Result<()> {
+    let base_url = std::env::var("FRONTEND_URL")?;
+    send(
+        to,
+        "Verify your AOT account",
+        format!("Click to verify your account: {base_url}/email/verify/confirm?token={token}"),
+    )
+    .await
+}
+
+pub async fn send_reset_email(to: &str, token: &str) -> anyhow::Result<()> {
+    let base_url = std::env::var("FRONTEND_URL")?;
+    send(
+        to,
+        "Reset your AOT password",
+        format!("Click to reset your password: {base_url}/resetpw/email/confirm?token={token}"),
+    )
+    .await
+}
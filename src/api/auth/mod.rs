@@ -2,26 +2,89 @@ use self::pragyan::PragyanMessage;
 use crate::api::error;
 use actix_session::Session;
 use actix_web::error::{ErrorBadRequest, ErrorUnauthorized};
+use actix_web::middleware::from_fn;
 use actix_web::web::{self, Data, Json};
 use actix_web::Responder;
-use actix_web::{HttpResponse, Result};
+use actix_web::{HttpRequest, HttpResponse, Result};
+use chrono::Utc;
 use diesel::r2d2::ConnectionManager;
 use diesel::PgConnection;
-use pwhash::bcrypt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+mod mailer;
+mod models;
+mod oauth;
 mod otp;
+mod password;
 mod pragyan;
+mod ratelimit;
+mod schema;
 pub mod session;
+mod totp;
 mod util;
 
-pub fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("/login").route(web::post().to(login)))
-        .service(web::resource("/logout").route(web::post().to(logout)))
-        .service(web::resource("/sendotp").route(web::post().to(sendotp)))
-        .service(web::resource("/verify").route(web::post().to(verify)))
-        .service(web::resource("/resetpw/sendotp").route(web::post().to(send_resetpw_otp)))
-        .service(web::resource("/resetpw/verify").route(web::post().to(reset_pw)));
+use ratelimit::RateLimiter;
+
+/// Builds the `RateLimiter` shared by every auth endpoint and spawns its
+/// background sweeper. Call this exactly once at application startup,
+/// outside the per-worker `HttpServer::new` factory, and pass the resulting
+/// handle into [`routes`] for every worker — constructing a fresh
+/// `RateLimiter` inside the factory would give each worker thread its own
+/// independent attempt counter, letting a client multiply its effective
+/// brute-force budget by the worker count just by spreading requests across
+/// them.
+///
+/// ```ignore
+/// let limiter = auth::rate_limiter();
+/// HttpServer::new(move || App::new().configure(auth::routes(limiter.clone())))
+/// ```
+pub fn rate_limiter() -> Data<RateLimiter> {
+    let limiter = Data::new(RateLimiter::new());
+    ratelimit::spawn_sweeper(limiter.clone());
+    limiter
+}
+
+/// Registers the auth routes and the `track_session` middleware that makes
+/// `/sessions/{id}` revocation take effect on the very next request. `limiter`
+/// should be built once via [`rate_limiter`] and shared across every worker.
+pub fn routes(limiter: Data<RateLimiter>) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(limiter).service(
+            web::scope("")
+                .wrap(from_fn(session::track_session))
+                .service(web::resource("/login").route(web::post().to(login)))
+                .service(web::resource("/logout").route(web::post().to(logout)))
+                .service(web::resource("/sendotp").route(web::post().to(sendotp)))
+                .service(web::resource("/verify").route(web::post().to(verify)))
+                .service(web::resource("/resetpw/sendotp").route(web::post().to(send_resetpw_otp)))
+                .service(web::resource("/resetpw/verify").route(web::post().to(reset_pw)))
+                .service(web::resource("/totp/enroll").route(web::post().to(totp_enroll)))
+                .service(web::resource("/totp/verify").route(web::post().to(totp_verify)))
+                .service(
+                    web::resource("/oauth/{provider}/authorize")
+                        .route(web::get().to(oauth_authorize)),
+                )
+                .service(
+                    web::resource("/oauth/{provider}/callback").route(web::get().to(oauth_callback)),
+                )
+                .service(web::resource("/sessions").route(web::get().to(list_sessions)))
+                .service(
+                    web::resource("/sessions/revoke-others")
+                        .route(web::post().to(revoke_other_sessions)),
+                )
+                .service(web::resource("/sessions/{id}").route(web::delete().to(revoke_session)))
+                .service(
+                    web::resource("/email/verify/send").route(web::post().to(send_email_verification)),
+                )
+                .service(
+                    web::resource("/email/verify/confirm")
+                        .route(web::post().to(confirm_email_verification)),
+                )
+                .service(web::resource("/resetpw/email/send").route(web::post().to(send_resetpw_email)))
+                .service(web::resource("/resetpw/email/confirm").route(web::post().to(reset_pw_email))),
+        );
+    }
 }
 
 #[derive(Deserialize)]
@@ -55,23 +118,91 @@ struct ResetPwVerifyRequest {
     recaptcha: String,
 }
 
-type Pool = diesel::r2d2::Pool<ConnectionManager<PgConnection>>;
+#[derive(Deserialize)]
+struct TotpVerifyRequest {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct EmailVerifyConfirmRequest {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct EmailResetRequest {
+    email: String,
+    recaptcha: String,
+}
+
+#[derive(Deserialize)]
+struct EmailResetConfirmRequest {
+    token: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+struct TotpEnrollResponse {
+    uri: String,
+}
+
+pub(crate) type Pool = diesel::r2d2::Pool<ConnectionManager<PgConnection>>;
+
+/// Records a server-side `user_session` row for a newly authenticated
+/// session and remembers its id so it can be looked up, listed and revoked.
+async fn establish_session(
+    pool: &Data<Pool>,
+    req: &HttpRequest,
+    session: &Session,
+    user_id: i32,
+) -> Result<()> {
+    let session_id = session::new_session_id();
+    let ip = ratelimit::client_ip(req);
+    let user_agent = session::user_agent(req);
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let sid = session_id.clone();
+    web::block(move || session::record_session(&conn, &sid, user_id, &ip, &user_agent))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("session_id", session_id)
+        .map_err(|err| error::handle_error(err.into()))?;
+    Ok(())
+}
 
 async fn login(
+    req: HttpRequest,
     request: web::Json<LoginRequest>,
     session: Session,
     pool: Data<Pool>,
+    limiter: Data<RateLimiter>,
 ) -> Result<impl Responder> {
     if session::is_signed_in(&session) {
         return Ok("Already signed in");
     }
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &request.username);
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
+
     let username = request.username.clone();
     let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let user = web::block(move || util::get_user_by_username(&conn, &username))
         .await
         .map_err(|err| error::handle_error(err.into()))?;
     if let Some(user) = user {
-        if !user.is_pragyan && bcrypt::verify(&request.password, &user.password) {
+        if !user.is_pragyan && password::verify(&request.password, &user.password) {
+            if password::needs_rehash(&user.password) {
+                let plaintext = request.password.clone();
+                let user_id = user.id;
+                let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+                web::block(move || {
+                    let new_hash = password::hash(&plaintext)?;
+                    util::update_password_hash(&conn, user_id, &new_hash)
+                })
+                .await
+                .map_err(|err| error::handle_error(err.into()))?;
+            }
+            limiter.record_success(&rate_limit_key);
             session
                 .set("user", user.id)
                 .map_err(|err| error::handle_error(err.into()))?;
@@ -79,10 +210,16 @@ async fn login(
                 session
                     .set("is_verified", true)
                     .map_err(|err| error::handle_error(err.into()))?;
+                establish_session(&pool, &req, &session, user.id).await?;
                 return Ok("Successfully Logged In");
             }
             // Account not verified
             return Err(ErrorUnauthorized("App account not verified"));
+        } else if !user.is_pragyan {
+            limiter.record_failure(&rate_limit_key);
+            return Err(ErrorUnauthorized(
+                "Invalid username/Pragyan email or password",
+            ));
         }
     }
 
@@ -95,20 +232,23 @@ async fn login(
     match pragyan_auth.status_code {
         200 => {
             if let PragyanMessage::Success(pragyan_user) = pragyan_auth.message {
+                let pool_for_block = pool.clone();
                 let user_id = web::block(move || {
-                    let conn = pool.get()?;
+                    let conn = pool_for_block.get()?;
                     let email = username.clone();
                     let name = pragyan_user.user_fullname;
                     util::get_pragyan_user(&conn, &email, &name)
                 })
                 .await
                 .map_err(|err| error::handle_error(err.into()))?;
+                limiter.record_success(&rate_limit_key);
                 session
                     .set("user", user_id)
                     .map_err(|err| error::handle_error(err.into()))?;
                 session
                     .set("is_verified", true)
                     .map_err(|err| error::handle_error(err.into()))?;
+                establish_session(&pool, &req, &session, user_id).await?;
                 Ok("Successfully Logged In")
             } else {
                 Err(anyhow::anyhow!(
@@ -119,24 +259,43 @@ async fn login(
             }
         }
         203 => Err(ErrorUnauthorized("Pragyan account not verified")),
-        _ => Err(ErrorUnauthorized(
-            "Invalid username/Pragyan email or password",
-        )),
+        _ => {
+            limiter.record_failure(&rate_limit_key);
+            Err(ErrorUnauthorized(
+                "Invalid username/Pragyan email or password",
+            ))
+        }
     }
 }
 
-async fn logout(session: Session) -> impl Responder {
+async fn logout(session: Session, pool: Data<Pool>) -> Result<impl Responder> {
+    if let Some(session_id) = session
+        .get::<String>("session_id")
+        .map_err(|err| error::handle_error(err.into()))?
+    {
+        let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+        web::block(move || session::delete_session(&conn, &session_id))
+            .await
+            .map_err(|err| error::handle_error(err.into()))?;
+    }
     session.clear();
-    HttpResponse::NoContent().finish()
+    Ok(HttpResponse::NoContent().finish())
 }
 
 async fn sendotp(
+    req: HttpRequest,
     pool: Data<Pool>,
     request: Json<OtpRequest>,
     session: Session,
+    limiter: Data<RateLimiter>,
 ) -> Result<impl Responder> {
-    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let user_id = session::get_unverified_user(&session)?;
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &user_id.to_string());
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let user = web::block(move || util::get_user(&conn, user_id))
         .await
         .map_err(|err| error::handle_error(err.into()))?;
@@ -148,6 +307,16 @@ async fn sendotp(
         return Err(ErrorBadRequest("User not found"));
     }
 
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let has_totp = web::block(move || util::user_has_totp(&conn, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    if has_totp {
+        return Err(ErrorBadRequest(
+            "Account uses an authenticator app, verify via /totp/verify",
+        ));
+    }
+
     let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let phone_number = user.clone().unwrap().phone;
     let duplicate_user = web::block(move || util::get_user_with_phone(&conn, &phone_number))
@@ -162,6 +331,7 @@ async fn sendotp(
         .await
         .map_err(|err| error::handle_error(err))?;
     if !is_valid_recatpcha {
+        limiter.record_failure(&rate_limit_key);
         return Err(ErrorUnauthorized("Invalid reCAPTCHA"));
     }
 
@@ -178,22 +348,30 @@ async fn sendotp(
         })
         .await
         .map_err(|err| error::handle_error(err.into()))?;
+        limiter.record_success(&rate_limit_key);
         Ok("OTP sent successfully")
     } else {
+        limiter.record_failure(&rate_limit_key);
         Err(ErrorBadRequest("Invalid phone number"))
     }
 }
 
 async fn verify(
+    req: HttpRequest,
     pool: Data<Pool>,
     request: Json<OtpVerifyRequest>,
     session: Session,
+    limiter: Data<RateLimiter>,
 ) -> Result<impl Responder> {
     let OtpVerifyRequest { otp, recaptcha } = request.into_inner();
     if otp.len() < 4 || otp.len() > 6 {
         return Err(ErrorBadRequest("Invalid OTP"));
     }
     let user_id = session::get_unverified_user(&session)?;
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &user_id.to_string());
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
     let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let user = web::block(move || util::get_user(&conn, user_id))
         .await
@@ -202,6 +380,16 @@ async fn verify(
         return Err(ErrorBadRequest("User not found"));
     }
 
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let has_totp = web::block(move || util::user_has_totp(&conn, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    if has_totp {
+        return Err(ErrorBadRequest(
+            "Account uses an authenticator app, verify via /totp/verify",
+        ));
+    }
+
     let is_valid_recatpcha = otp::verify_recaptcha(recaptcha)
         .await
         .map_err(|err| error::handle_error(err))?;
@@ -219,8 +407,9 @@ async fn verify(
         .map_err(|err| error::handle_error(err))?;
     match two_factor_response.details.as_str() {
         "OTP Matched" => {
+            let pool_for_block = pool.clone();
             web::block(move || {
-                let conn = pool.get()?;
+                let conn = pool_for_block.get()?;
                 util::verify_user(&conn, user_id)
             })
             .await
@@ -228,23 +417,36 @@ async fn verify(
             session
                 .set("is_verified", true)
                 .map_err(|err| error::handle_error(err.into()))?;
+            establish_session(&pool, &req, &session, user_id).await?;
+            limiter.record_success(&rate_limit_key);
             Ok("Account successfully verified")
         }
         "OTP Expired" => Err(ErrorUnauthorized("OTP Expired")),
-        _ => Err(ErrorUnauthorized("OTP Mismatch")),
+        _ => {
+            limiter.record_failure(&rate_limit_key);
+            Err(ErrorUnauthorized("OTP Mismatch"))
+        }
     }
 }
 
 async fn send_resetpw_otp(
+    req: HttpRequest,
     pool: Data<Pool>,
     request: Json<ResetPwRequest>,
+    limiter: Data<RateLimiter>,
 ) -> Result<impl Responder> {
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &request.phone_number);
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
+
     let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let phone_number = request.phone_number.clone();
     let user = web::block(move || util::get_user_with_phone(&conn, &phone_number))
         .await
         .map_err(|err| error::handle_error(err.into()))?;
     if user.is_none() {
+        limiter.record_failure(&rate_limit_key);
         return Err(ErrorBadRequest("Invalid phone number"));
     }
 
@@ -254,6 +456,7 @@ async fn send_resetpw_otp(
         .await
         .map_err(|err| error::handle_error(err))?;
     if !is_valid_recatpcha {
+        limiter.record_failure(&rate_limit_key);
         return Err(ErrorUnauthorized("Invalid reCAPTCHA"));
     }
 
@@ -271,13 +474,20 @@ async fn send_resetpw_otp(
         })
         .await
         .map_err(|err| error::handle_error(err.into()))?;
+        limiter.record_success(&rate_limit_key);
         Ok("OTP sent successfully")
     } else {
+        limiter.record_failure(&rate_limit_key);
         Err(ErrorBadRequest("Invalid phone number"))
     }
 }
 
-async fn reset_pw(pool: Data<Pool>, request: Json<ResetPwVerifyRequest>) -> Result<impl Responder> {
+async fn reset_pw(
+    req: HttpRequest,
+    pool: Data<Pool>,
+    request: Json<ResetPwVerifyRequest>,
+    limiter: Data<RateLimiter>,
+) -> Result<impl Responder> {
     let ResetPwVerifyRequest {
         phone_number,
         otp,
@@ -287,6 +497,10 @@ async fn reset_pw(pool: Data<Pool>, request: Json<ResetPwVerifyRequest>) -> Resu
     if otp.len() < 4 || otp.len() > 6 {
         return Err(ErrorBadRequest("Invalid OTP"));
     }
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &phone_number);
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
     let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
     let phone = phone_number.clone();
     let user = web::block(move || util::get_user_with_phone(&conn, &phone))
@@ -315,13 +529,383 @@ async fn reset_pw(pool: Data<Pool>, request: Json<ResetPwVerifyRequest>) -> Resu
         "OTP Matched" => {
             web::block(move || {
                 let conn = pool.get()?;
-                util::reset_password(&conn, &phone_number, &password)
+                let password_hash = password::hash(&password)?;
+                util::reset_password(&conn, &phone_number, &password_hash)
             })
             .await
             .map_err(|err| error::handle_error(err.into()))?;
+            limiter.record_success(&rate_limit_key);
             Ok("Password reset successfully")
         }
         "OTP Expired" => Err(ErrorUnauthorized("OTP Expired")),
-        _ => Err(ErrorUnauthorized("OTP Mismatch")),
+        _ => {
+            limiter.record_failure(&rate_limit_key);
+            Err(ErrorUnauthorized("OTP Mismatch"))
+        }
+    }
+}
+
+async fn totp_enroll(pool: Data<Pool>, session: Session) -> Result<impl Responder> {
+    let user_id = session::get_unverified_user(&session)?;
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let user = web::block(move || util::get_user(&conn, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    let user = user.ok_or_else(|| ErrorBadRequest("User not found"))?;
+
+    let secret = totp::generate_secret();
+    let secret_base32 = totp::encode_secret(&secret);
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let encoded = secret_base32.clone();
+    web::block(move || util::set_totp_secret(&conn, user_id, &encoded))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    let uri = totp::provisioning_uri(&user.username, &secret_base32);
+    Ok(Json(TotpEnrollResponse { uri }))
+}
+
+async fn totp_verify(
+    req: HttpRequest,
+    pool: Data<Pool>,
+    request: Json<TotpVerifyRequest>,
+    session: Session,
+    limiter: Data<RateLimiter>,
+) -> Result<impl Responder> {
+    let code = request.into_inner().code;
+    let user_id = session::get_unverified_user(&session)?;
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &user_id.to_string());
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let secret_base32 = web::block(move || util::get_totp_secret(&conn, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    let secret_base32 = secret_base32.ok_or_else(|| ErrorBadRequest("TOTP not enrolled"))?;
+    let secret =
+        totp::decode_secret(&secret_base32).ok_or_else(|| ErrorBadRequest("Invalid TOTP secret"))?;
+
+    let unix_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| error::handle_error(err.into()))?
+        .as_secs();
+    if !totp::verify_code(&secret, &code, unix_time) {
+        limiter.record_failure(&rate_limit_key);
+        return Err(ErrorUnauthorized("Invalid TOTP code"));
+    }
+
+    let pool_for_block = pool.clone();
+    web::block(move || {
+        let conn = pool_for_block.get()?;
+        util::verify_user(&conn, user_id)
+    })
+    .await
+    .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("is_verified", true)
+        .map_err(|err| error::handle_error(err.into()))?;
+    establish_session(&pool, &req, &session, user_id).await?;
+    limiter.record_success(&rate_limit_key);
+    Ok("Account successfully verified")
+}
+
+#[derive(Deserialize)]
+struct OAuthCallbackQuery {
+    code: String,
+    state: String,
+}
+
+async fn oauth_authorize(path: web::Path<String>, session: Session) -> Result<impl Responder> {
+    let provider = path.into_inner();
+    let flow = oauth::build_authorize_url(&provider)
+        .await
+        .map_err(|err| error::handle_error(err))?;
+
+    session
+        .set("oauth_provider", provider)
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("oauth_state", flow.csrf_state)
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("oauth_pkce_verifier", flow.pkce_verifier)
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("oauth_nonce", flow.nonce)
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    Ok(HttpResponse::Found()
+        .insert_header(("Location", flow.auth_url))
+        .finish())
+}
+
+async fn oauth_callback(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<OAuthCallbackQuery>,
+    session: Session,
+    pool: Data<Pool>,
+) -> Result<impl Responder> {
+    let provider = path.into_inner();
+    let expected_provider: Option<String> = session
+        .get("oauth_provider")
+        .map_err(|err| error::handle_error(err.into()))?;
+    let expected_state: Option<String> = session
+        .get("oauth_state")
+        .map_err(|err| error::handle_error(err.into()))?;
+    let pkce_verifier: Option<String> = session
+        .get("oauth_pkce_verifier")
+        .map_err(|err| error::handle_error(err.into()))?;
+    let nonce: Option<String> = session
+        .get("oauth_nonce")
+        .map_err(|err| error::handle_error(err.into()))?;
+    session.remove("oauth_provider");
+    session.remove("oauth_state");
+    session.remove("oauth_pkce_verifier");
+    session.remove("oauth_nonce");
+
+    let (expected_provider, expected_state, pkce_verifier, nonce) =
+        match (expected_provider, expected_state, pkce_verifier, nonce) {
+            (Some(p), Some(s), Some(v), Some(n)) => (p, s, v, n),
+            _ => return Err(ErrorBadRequest("No pending OAuth flow")),
+        };
+    if provider != expected_provider {
+        return Err(ErrorBadRequest("OAuth provider mismatch"));
+    }
+    if query.state != expected_state {
+        return Err(ErrorBadRequest("Invalid OAuth state"));
+    }
+
+    let identity = oauth::exchange_and_verify(&provider, &query.code, &pkce_verifier, &nonce)
+        .await
+        .map_err(|err| error::handle_error(err))?;
+
+    let pool_for_block = pool.clone();
+    let user_id = web::block(move || {
+        let conn = pool_for_block.get()?;
+        util::get_oauth_user(&conn, &identity.email, &identity.name)
+    })
+    .await
+    .map_err(|err| error::handle_error(err.into()))?;
+
+    session
+        .set("user", user_id)
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("is_verified", true)
+        .map_err(|err| error::handle_error(err.into()))?;
+    establish_session(&pool, &req, &session, user_id).await?;
+    Ok("Successfully Logged In")
+}
+
+fn current_session_id(session: &Session) -> Result<String> {
+    session
+        .get::<String>("session_id")
+        .map_err(|err| error::handle_error(err.into()))?
+        .ok_or_else(|| ErrorUnauthorized("No active server-side session"))
+}
+
+async fn list_sessions(session: Session, pool: Data<Pool>) -> Result<impl Responder> {
+    let user_id = session::get_unverified_user(&session)?;
+    if !session::is_verified(&session) {
+        return Err(ErrorUnauthorized("Account not verified"));
+    }
+    let current_session_id = current_session_id(&session)?;
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let sessions = web::block(move || session::list_sessions(&conn, user_id, &current_session_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(sessions))
+}
+
+async fn revoke_session(
+    session: Session,
+    pool: Data<Pool>,
+    path: web::Path<String>,
+) -> Result<impl Responder> {
+    let user_id = session::get_unverified_user(&session)?;
+    if !session::is_verified(&session) {
+        return Err(ErrorUnauthorized("Account not verified"));
+    }
+    let target_session_id = path.into_inner();
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let revoked = web::block(move || session::revoke_session(&conn, &target_session_id, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    if !revoked {
+        return Err(ErrorBadRequest("No such session"));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn revoke_other_sessions(session: Session, pool: Data<Pool>) -> Result<impl Responder> {
+    let user_id = session::get_unverified_user(&session)?;
+    if !session::is_verified(&session) {
+        return Err(ErrorUnauthorized("Account not verified"));
+    }
+    let current_session_id = current_session_id(&session)?;
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let revoked = web::block(move || {
+        session::revoke_other_sessions(&conn, user_id, &current_session_id)
+    })
+    .await
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok(Json(revoked))
+}
+
+async fn send_email_verification(
+    pool: Data<Pool>,
+    request: Json<OtpRequest>,
+    session: Session,
+) -> Result<impl Responder> {
+    let user_id = session::get_unverified_user(&session)?;
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let user = web::block(move || util::get_user(&conn, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    let user = user.ok_or_else(|| ErrorBadRequest("User not found"))?;
+    if user.is_verified {
+        return Err(ErrorBadRequest("Account already verified"));
+    }
+
+    let is_valid_recatpcha = otp::verify_recaptcha(request.into_inner().recaptcha)
+        .await
+        .map_err(|err| error::handle_error(err))?;
+    if !is_valid_recatpcha {
+        return Err(ErrorUnauthorized("Invalid reCAPTCHA"));
+    }
+
+    let token = mailer::generate_token();
+    let expires_at = mailer::expiry();
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let stored_token = token.clone();
+    web::block(move || util::set_email_verification_token(&conn, user_id, &stored_token, expires_at))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    mailer::send_verification_email(&user.email, &token)
+        .await
+        .map_err(|err| error::handle_error(err))?;
+    Ok("Verification email sent")
+}
+
+async fn confirm_email_verification(
+    req: HttpRequest,
+    pool: Data<Pool>,
+    request: Json<EmailVerifyConfirmRequest>,
+    session: Session,
+) -> Result<impl Responder> {
+    let token = request.into_inner().token;
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let lookup_token = token.clone();
+    let verification = web::block(move || util::get_email_verification(&conn, &lookup_token))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    let (user_id, expires_at) =
+        verification.ok_or_else(|| ErrorBadRequest("Invalid verification token"))?;
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let consume_token = token.clone();
+    web::block(move || util::consume_email_verification(&conn, &consume_token))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    if Utc::now().naive_utc() > expires_at {
+        return Err(ErrorUnauthorized("Verification link expired"));
     }
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    web::block(move || util::verify_user(&conn, user_id))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("user", user_id)
+        .map_err(|err| error::handle_error(err.into()))?;
+    session
+        .set("is_verified", true)
+        .map_err(|err| error::handle_error(err.into()))?;
+    establish_session(&pool, &req, &session, user_id).await?;
+    Ok("Account successfully verified")
+}
+
+async fn send_resetpw_email(
+    req: HttpRequest,
+    pool: Data<Pool>,
+    request: Json<EmailResetRequest>,
+    limiter: Data<RateLimiter>,
+) -> Result<impl Responder> {
+    let rate_limit_key = RateLimiter::key(&ratelimit::client_ip(&req), &request.email);
+    limiter
+        .check(&rate_limit_key)
+        .map_err(|err| ratelimit::too_many_requests(err.retry_after))?;
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let email = request.email.clone();
+    let user = web::block(move || util::get_user_by_email(&conn, &email))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    let user = match user {
+        Some(user) => user,
+        None => {
+            limiter.record_failure(&rate_limit_key);
+            return Err(ErrorBadRequest("Invalid email"));
+        }
+    };
+
+    let request = request.into_inner();
+    let is_valid_recatpcha = otp::verify_recaptcha(request.recaptcha)
+        .await
+        .map_err(|err| error::handle_error(err))?;
+    if !is_valid_recatpcha {
+        limiter.record_failure(&rate_limit_key);
+        return Err(ErrorUnauthorized("Invalid reCAPTCHA"));
+    }
+
+    let token = mailer::generate_token();
+    let expires_at = mailer::expiry();
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let stored_token = token.clone();
+    let user_id = user.id;
+    web::block(move || util::set_email_reset_token(&conn, user_id, &stored_token, expires_at))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+
+    mailer::send_reset_email(&request.email, &token)
+        .await
+        .map_err(|err| error::handle_error(err))?;
+    limiter.record_success(&rate_limit_key);
+    Ok("Password reset email sent")
+}
+
+async fn reset_pw_email(
+    pool: Data<Pool>,
+    request: Json<EmailResetConfirmRequest>,
+) -> Result<impl Responder> {
+    let EmailResetConfirmRequest { token, password } = request.into_inner();
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let lookup_token = token.clone();
+    let reset = web::block(move || util::get_email_reset_token(&conn, &lookup_token))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    let (user_id, expires_at) = reset.ok_or_else(|| ErrorBadRequest("Invalid reset token"))?;
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    let consume_token = token.clone();
+    web::block(move || util::consume_email_reset_token(&conn, &consume_token))
+        .await
+        .map_err(|err| error::handle_error(err.into()))?;
+    if Utc::now().naive_utc() > expires_at {
+        return Err(ErrorUnauthorized("Reset link expired"));
+    }
+
+    let conn = pool.get().map_err(|err| error::handle_error(err.into()))?;
+    web::block(move || {
+        let password_hash = password::hash(&password)?;
+        util::reset_password_for_user(&conn, user_id, &password_hash)
+    })
+    .await
+    .map_err(|err| error::handle_error(err.into()))?;
+    Ok("Password reset successfully")
 }
\ No newline at end of file
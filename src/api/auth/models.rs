@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable};
+
+use super::schema::{email_reset_token, email_verification_token, user_session, user_totp, users};
+
+#[derive(Queryable, Identifiable, Clone)]
+#[table_name = "users"]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub password: String,
+    pub email: String,
+    pub phone: String,
+    pub is_pragyan: bool,
+    pub is_verified: bool,
+    pub otp_session_id: Option<String>,
+}
+
+#[derive(Queryable, Identifiable, Insertable)]
+#[table_name = "user_totp"]
+#[primary_key(user_id)]
+pub struct UserTotp {
+    pub user_id: i32,
+    pub secret: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Identifiable, Insertable)]
+#[table_name = "user_session"]
+pub struct UserSession {
+    pub id: String,
+    pub user_id: i32,
+    pub ip: String,
+    pub device: String,
+    pub created_at: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "email_verification_token"]
+pub struct NewEmailVerificationToken {
+    pub token: String,
+    pub user_id: i32,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "email_reset_token"]
+pub struct NewEmailResetToken {
+    pub token: String,
+    pub user_id: i32,
+    pub expires_at: NaiveDateTime,
+}
@@ -0,0 +1,112 @@
+use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+    AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce,
+    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+};
+
+/// State handed back to the caller after building an authorization URL; the
+/// handler stashes these in the session and replays them on the callback.
+pub struct AuthorizeFlow {
+    pub auth_url: String,
+    pub csrf_state: String,
+    pub pkce_verifier: String,
+    pub nonce: String,
+}
+
+pub struct VerifiedIdentity {
+    pub email: String,
+    pub name: String,
+}
+
+fn issuer_url(provider: &str) -> anyhow::Result<&'static str> {
+    match provider {
+        "google" => Ok("https://accounts.google.com"),
+        _ => anyhow::bail!("unsupported OAuth provider: {provider}"),
+    }
+}
+
+fn env_var(provider: &str, suffix: &str) -> anyhow::Result<String> {
+    let key = format!("{}_{}", provider.to_uppercase(), suffix);
+    std::env::var(&key).map_err(|_| anyhow::anyhow!("{key} is not configured"))
+}
+
+async fn client_for(provider: &str) -> anyhow::Result<CoreClient> {
+    let issuer = IssuerUrl::new(issuer_url(provider)?.to_string())?;
+    let metadata = CoreProviderMetadata::discover_async(issuer, async_http_client).await?;
+    let client_id = ClientId::new(env_var(provider, "CLIENT_ID")?);
+    let client_secret = ClientSecret::new(env_var(provider, "CLIENT_SECRET")?);
+    let redirect_url = RedirectUrl::new(env_var(provider, "REDIRECT_URL")?)?;
+
+    Ok(
+        CoreClient::from_provider_metadata(metadata, client_id, Some(client_secret))
+            .set_redirect_uri(redirect_url),
+    )
+}
+
+/// Builds the provider's authorization URL with a CSRF `state` and a PKCE
+/// `code_challenge`, returning everything the caller must remember for the
+/// matching callback.
+pub async fn build_authorize_url(provider: &str) -> anyhow::Result<AuthorizeFlow> {
+    let client = client_for(provider).await?;
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let (auth_url, csrf_state, nonce) = client
+        .authorize_url(
+            AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    Ok(AuthorizeFlow {
+        auth_url: auth_url.to_string(),
+        csrf_state: csrf_state.secret().clone(),
+        pkce_verifier: pkce_verifier.secret().clone(),
+        nonce: nonce.secret().clone(),
+    })
+}
+
+/// Exchanges the authorization `code` for tokens, then verifies the
+/// `id_token`'s signature and `aud`/`iss` claims against `nonce`.
+pub async fn exchange_and_verify(
+    provider: &str,
+    code: &str,
+    pkce_verifier: &str,
+    nonce: &str,
+) -> anyhow::Result<VerifiedIdentity> {
+    let client = client_for(provider).await?;
+
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code.to_string()))
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier.to_string()))
+        .request_async(async_http_client)
+        .await
+        .map_err(|err| anyhow::anyhow!("token exchange failed: {err}"))?;
+
+    let id_token = token_response
+        .extra_fields()
+        .id_token()
+        .ok_or_else(|| anyhow::anyhow!("provider did not return an id_token"))?;
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &Nonce::new(nonce.to_string()))
+        .map_err(|err| anyhow::anyhow!("id_token verification failed: {err}"))?;
+
+    if claims.email_verified() != Some(true) {
+        anyhow::bail!("provider's email is not verified");
+    }
+    let email = claims
+        .email()
+        .ok_or_else(|| anyhow::anyhow!("provider did not return an email"))?
+        .to_string();
+    let name = claims
+        .name()
+        .and_then(|names| names.get(None))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| email.clone());
+
+    Ok(VerifiedIdentity { email, name })
+}
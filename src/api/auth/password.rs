@@ -0,0 +1,48 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use pwhash::bcrypt;
+
+// m=19 MiB, t=2, p=1 — OWASP's minimum recommendation for Argon2id.
+const MEM_COST_KIB: u32 = 19 * 1024;
+const TIME_COST: u32 = 2;
+const PARALLELISM: u32 = 1;
+
+const ARGON2ID_PREFIX: &str = "$argon2id$";
+
+fn argon2() -> Argon2<'static> {
+    let params = Params::new(MEM_COST_KIB, TIME_COST, PARALLELISM, None)
+        .expect("hard-coded Argon2id parameters are valid");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Hashes `password` with Argon2id, PHC-encoded for storage.
+pub fn hash(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    argon2()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| anyhow::anyhow!("failed to hash password: {err}"))
+}
+
+/// Verifies `password` against `stored_hash`, transparently supporting both
+/// the legacy bcrypt format (`$2...`) and the current Argon2id format.
+pub fn verify(password: &str, stored_hash: &str) -> bool {
+    if stored_hash.starts_with(ARGON2ID_PREFIX) {
+        PasswordHash::new(stored_hash)
+            .map(|parsed| {
+                argon2()
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    } else {
+        bcrypt::verify(password, stored_hash)
+    }
+}
+
+/// True when `stored_hash` is the legacy bcrypt format and should be
+/// transparently upgraded to Argon2id now that the password is known.
+pub fn needs_rehash(stored_hash: &str) -> bool {
+    stored_hash.starts_with("$2")
+}
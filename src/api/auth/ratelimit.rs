@@ -0,0 +1,235 @@
+use actix_web::http::header;
+use actix_web::{HttpResponse, ResponseError};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_FAILURES: u32 = 5;
+const DEFAULT_BASE_BACKOFF_SECS: u64 = 30;
+const MAX_BACKOFF_SECS: u64 = 15 * 60;
+const STALE_AFTER_SECS: u64 = 24 * 60 * 60;
+
+struct AttemptState {
+    failures: u32,
+    locked_until: Option<Instant>,
+    last_seen: Instant,
+}
+
+impl AttemptState {
+    fn fresh() -> Self {
+        AttemptState {
+            failures: 0,
+            locked_until: None,
+            last_seen: Instant::now(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many attempts, try again later")
+    }
+}
+
+impl ResponseError for RateLimited {
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, self.retry_after.as_secs().to_string()))
+            .body("Too many attempts, try again later")
+    }
+}
+
+/// Shared `actix_web::Data` guarding login/OTP endpoints against brute force.
+///
+/// Keyed on `(client_ip, username_or_phone)`, so failures from one IP against
+/// an account don't lock out the same account from other IPs. Each IP keeps
+/// its own counter per account; a client that switches IPs starts a fresh one.
+pub struct RateLimiter {
+    attempts: Mutex<HashMap<String, AttemptState>>,
+    max_failures: u32,
+    base_backoff: Duration,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        let max_failures = std::env::var("RATE_LIMIT_MAX_FAILURES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_FAILURES);
+        let base_backoff = std::env::var("RATE_LIMIT_BASE_BACKOFF_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| Duration::from_secs(DEFAULT_BASE_BACKOFF_SECS));
+        RateLimiter {
+            attempts: Mutex::new(HashMap::new()),
+            max_failures,
+            base_backoff,
+        }
+    }
+
+    pub fn key(client_ip: &str, identifier: &str) -> String {
+        format!("{client_ip}:{identifier}")
+    }
+
+    /// Returns an error carrying `Retry-After` if `key` is currently locked out.
+    pub fn check(&self, key: &str) -> Result<(), RateLimited> {
+        let attempts = self.attempts.lock().unwrap();
+        if let Some(state) = attempts.get(key) {
+            if let Some(locked_until) = state.locked_until {
+                let now = Instant::now();
+                if now < locked_until {
+                    // Round up to whole seconds so `Retry-After` never points
+                    // to a moment before the lock lifts.
+                    let remaining = locked_until - now;
+                    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                    return Err(RateLimited {
+                        retry_after: Duration::from_secs(secs),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a failed attempt, locking `key` out with exponential backoff
+    /// once `max_failures` consecutive failures have been seen.
+    pub fn record_failure(&self, key: &str) {
+        let mut attempts = self.attempts.lock().unwrap();
+        let state = attempts
+            .entry(key.to_string())
+            .or_insert_with(AttemptState::fresh);
+        state.failures += 1;
+        state.last_seen = Instant::now();
+        if state.failures >= self.max_failures {
+            let extra = state.failures - self.max_failures;
+            let backoff_secs =
+                (self.base_backoff.as_secs().saturating_mul(2u64.saturating_pow(extra)))
+                    .min(MAX_BACKOFF_SECS);
+            state.locked_until = Some(Instant::now() + Duration::from_secs(backoff_secs));
+        }
+    }
+
+    /// Resets the counter for `key` after a successful authentication.
+    pub fn record_success(&self, key: &str) {
+        self.attempts.lock().unwrap().remove(key);
+    }
+
+    /// Drops entries that haven't been touched in a while so the map doesn't
+    /// grow unbounded. Intended to be called periodically by a background task.
+    pub fn sweep(&self) {
+        let now = Instant::now();
+        self.attempts
+            .lock()
+            .unwrap()
+            .retain(|_, state| now.duration_since(state.last_seen).as_secs() < STALE_AFTER_SECS);
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extracts the caller's IP for rate-limit keying, preferring the
+/// connection's peer address over proxy-supplied headers that a client
+/// could otherwise spoof to dodge the limiter.
+pub fn client_ip(req: &actix_web::HttpRequest) -> String {
+    req.peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub fn too_many_requests(retry_after: Duration) -> actix_web::Error {
+    RateLimited { retry_after }.into()
+}
+
+/// Periodically sweeps stale entries out of `limiter`. Meant to be spawned
+/// once at app startup alongside the `Data<RateLimiter>` registration.
+pub fn spawn_sweeper(limiter: actix_web::web::Data<RateLimiter>) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(300));
+        loop {
+            interval.tick().await;
+            limiter.sweep();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_failures: u32, base_backoff: Duration) -> RateLimiter {
+        RateLimiter {
+            attempts: Mutex::new(HashMap::new()),
+            max_failures,
+            base_backoff,
+        }
+    }
+
+    #[test]
+    fn locks_on_the_nth_failure() {
+        let limiter = limiter(5, Duration::from_secs(30));
+        for _ in 0..4 {
+            limiter.record_failure("k");
+            assert!(limiter.check("k").is_ok());
+        }
+        limiter.record_failure("k");
+        assert!(limiter.check("k").is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_per_extra_failure_and_caps_at_max() {
+        let limiter = limiter(5, Duration::from_secs(30));
+        for _ in 0..5 {
+            limiter.record_failure("k");
+        }
+        let retry_after = limiter.check("k").unwrap_err().retry_after;
+        assert_eq!(retry_after.as_secs(), 30);
+
+        limiter.record_failure("k");
+        let retry_after = limiter.check("k").unwrap_err().retry_after;
+        assert_eq!(retry_after.as_secs(), 60);
+
+        for _ in 0..20 {
+            limiter.record_failure("k");
+        }
+        let retry_after = limiter.check("k").unwrap_err().retry_after;
+        assert_eq!(retry_after.as_secs(), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn record_success_clears_the_counter() {
+        let limiter = limiter(5, Duration::from_secs(30));
+        for _ in 0..5 {
+            limiter.record_failure("k");
+        }
+        assert!(limiter.check("k").is_err());
+        limiter.record_success("k");
+        assert!(limiter.check("k").is_ok());
+    }
+
+    #[test]
+    fn sweep_evicts_only_stale_entries() {
+        let limiter = limiter(5, Duration::from_secs(30));
+        limiter.record_failure("stale");
+        limiter.record_failure("fresh");
+        {
+            let mut attempts = limiter.attempts.lock().unwrap();
+            attempts.get_mut("stale").unwrap().last_seen =
+                Instant::now() - Duration::from_secs(STALE_AFTER_SECS + 1);
+        }
+        limiter.sweep();
+        let attempts = limiter.attempts.lock().unwrap();
+        assert!(!attempts.contains_key("stale"));
+        assert!(attempts.contains_key("fresh"));
+    }
+}
@@ -0,0 +1,69 @@
+//! Diesel table definitions for the tables this module reads and writes.
+//!
+//! `users` mirrors the project's existing table and isn't created by a
+//! migration in this module; the rest are introduced by migrations alongside
+//! the auth features that need them.
+
+diesel::table! {
+    users (id) {
+        id -> Int4,
+        username -> Varchar,
+        password -> Varchar,
+        email -> Varchar,
+        phone -> Varchar,
+        is_pragyan -> Bool,
+        is_verified -> Bool,
+        otp_session_id -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    user_totp (user_id) {
+        user_id -> Int4,
+        secret -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_session (id) {
+        id -> Varchar,
+        user_id -> Int4,
+        ip -> Varchar,
+        device -> Varchar,
+        created_at -> Timestamp,
+        last_seen -> Timestamp,
+    }
+}
+
+// Verification and reset tokens live in separate tables (rather than one
+// table with a "purpose" column) so a verification link can never be
+// replayed as a password-reset link or vice versa.
+diesel::table! {
+    email_verification_token (token) {
+        token -> Varchar,
+        user_id -> Int4,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    email_reset_token (token) {
+        token -> Varchar,
+        user_id -> Int4,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(user_totp -> users (user_id));
+diesel::joinable!(user_session -> users (user_id));
+diesel::joinable!(email_verification_token -> users (user_id));
+diesel::joinable!(email_reset_token -> users (user_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    users,
+    user_totp,
+    user_session,
+    email_verification_token,
+    email_reset_token,
+);
@@ -0,0 +1,165 @@
+use actix_session::{Session, SessionExt};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorUnauthorized;
+use actix_web::middleware::Next;
+use actix_web::web::{self, Data};
+use actix_web::Error;
+use chrono::NaiveDateTime;
+use diesel::r2d2::ConnectionManager;
+use diesel::PgConnection;
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::util;
+use super::Pool;
+
+pub fn is_signed_in(session: &Session) -> bool {
+    matches!(session.get::<i32>("user"), Ok(Some(_)))
+}
+
+/// Returns the id of the user attached to `session`, regardless of whether
+/// they've completed the OTP/TOTP/OAuth verification step yet.
+pub fn get_unverified_user(session: &Session) -> Result<i32, Error> {
+    session
+        .get::<i32>("user")
+        .map_err(|err| ErrorUnauthorized(err.to_string()))?
+        .ok_or_else(|| ErrorUnauthorized("Not signed in"))
+}
+
+pub fn is_verified(session: &Session) -> bool {
+    matches!(session.get::<bool>("is_verified"), Ok(Some(true)))
+}
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub created_at: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    pub ip: String,
+    pub device: String,
+    pub is_current: bool,
+}
+
+/// Reads the `User-Agent` header, defaulting to an empty string if absent.
+pub fn user_agent(req: &actix_web::HttpRequest) -> String {
+    req.headers()
+        .get(actix_web::http::header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Generates the id for a new server-side session row. Kept separate from
+/// actix-session's own cookie id so it can be looked up, listed and revoked
+/// independently of the cookie machinery.
+pub fn new_session_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// A rough "browser on OS" label for the sessions list; not meant to be a
+/// precise device fingerprint.
+pub fn describe_device(user_agent: &str) -> String {
+    woothee::parser::Parser::new()
+        .parse(user_agent)
+        .map(|parsed| format!("{} on {}", parsed.name, parsed.os))
+        .unwrap_or_else(|| "Unknown device".to_string())
+}
+
+/// Inserts the server-side row for a freshly established session. Called
+/// from its own `web::block` by the handlers right after the session is
+/// granted.
+pub fn record_session(
+    conn: &PgConnection,
+    session_id: &str,
+    user_id: i32,
+    ip: &str,
+    user_agent: &str,
+) -> anyhow::Result<()> {
+    util::insert_user_session(conn, session_id, user_id, ip, &describe_device(user_agent))
+}
+
+pub fn touch_session(conn: &PgConnection, session_id: &str) -> anyhow::Result<()> {
+    util::touch_user_session(conn, session_id)
+}
+
+pub fn list_sessions(
+    conn: &PgConnection,
+    user_id: i32,
+    current_session_id: &str,
+) -> anyhow::Result<Vec<SessionInfo>> {
+    let rows = util::get_user_sessions(conn, user_id)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| SessionInfo {
+            is_current: row.id == current_session_id,
+            id: row.id,
+            created_at: row.created_at,
+            last_seen: row.last_seen,
+            ip: row.ip,
+            device: row.device,
+        })
+        .collect())
+}
+
+/// Revokes a single session belonging to `user_id`. Returns `false` if no
+/// such session exists (either it never did, or it belongs to someone else).
+pub fn revoke_session(conn: &PgConnection, session_id: &str, user_id: i32) -> anyhow::Result<bool> {
+    util::delete_user_session_for_user(conn, session_id, user_id)
+}
+
+/// Revokes every session for `user_id` except `current_session_id`.
+pub fn revoke_other_sessions(
+    conn: &PgConnection,
+    user_id: i32,
+    current_session_id: &str,
+) -> anyhow::Result<usize> {
+    util::delete_other_user_sessions(conn, user_id, current_session_id)
+}
+
+pub fn delete_session(conn: &PgConnection, session_id: &str) -> anyhow::Result<()> {
+    util::delete_user_session(conn, session_id)
+}
+
+/// A session whose id is absent from `user_session` must be treated as
+/// unauthenticated even if the cookie itself still validates — this is what
+/// makes `/sessions/{id}` revocation take effect immediately.
+pub fn is_session_valid(conn: &PgConnection, session_id: &str) -> anyhow::Result<bool> {
+    util::user_session_exists(conn, session_id)
+}
+
+/// Validates the request's session id against `user_session` on every
+/// authenticated request, purging the cookie session if it's been revoked,
+/// and otherwise bumping `last_seen`. Has no effect on requests that never
+/// signed in.
+pub async fn track_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let session = req.get_session();
+    let session_id: Option<String> = session.get("session_id").unwrap_or(None);
+
+    if let (Some(session_id), Some(pool)) = (session_id, req.app_data::<Data<Pool>>().cloned()) {
+        let lookup_id = session_id.clone();
+        let valid = web::block(move || {
+            let conn = pool.get()?;
+            is_session_valid(&conn, &lookup_id)
+        })
+        .await
+        .ok()
+        .and_then(|res| res.ok())
+        .unwrap_or(false);
+
+        if !valid {
+            session.purge();
+        } else if let Some(pool) = req.app_data::<Data<Pool>>().cloned() {
+            let _ = web::block(move || {
+                let conn = pool.get()?;
+                touch_session(&conn, &session_id)
+            })
+            .await;
+        }
+    }
+
+    next.call(req).await
+}
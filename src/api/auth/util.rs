@@ -0,0 +1,300 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use super::models::{
+    NewEmailResetToken, NewEmailVerificationToken, User, UserSession, UserTotp,
+};
+use super::password;
+use super::schema::{email_reset_token, email_verification_token, user_session, user_totp, users};
+
+pub fn get_user_by_username(conn: &PgConnection, name: &str) -> anyhow::Result<Option<User>> {
+    Ok(users::table
+        .filter(users::username.eq(name))
+        .first::<User>(conn)
+        .optional()?)
+}
+
+pub fn get_user(conn: &PgConnection, id: i32) -> anyhow::Result<Option<User>> {
+    Ok(users::table
+        .filter(users::id.eq(id))
+        .first::<User>(conn)
+        .optional()?)
+}
+
+pub fn get_user_with_phone(conn: &PgConnection, phone_number: &str) -> anyhow::Result<Option<User>> {
+    Ok(users::table
+        .filter(users::phone.eq(phone_number))
+        .first::<User>(conn)
+        .optional()?)
+}
+
+pub fn get_user_by_email(conn: &PgConnection, email_address: &str) -> anyhow::Result<Option<User>> {
+    Ok(users::table
+        .filter(users::email.eq(email_address))
+        .first::<User>(conn)
+        .optional()?)
+}
+
+/// Looks up a Pragyan user by email, creating a local account on first login.
+/// Pragyan users authenticate entirely through Pragyan, so the stored
+/// password is an unusable Argon2id hash of random bytes rather than
+/// anything the user ever sets or types.
+pub fn get_pragyan_user(conn: &PgConnection, email_address: &str, name: &str) -> anyhow::Result<i32> {
+    if let Some(user) = get_user_by_email(conn, email_address)? {
+        return Ok(user.id);
+    }
+    let placeholder_hash = password::hash(&Uuid::new_v4().to_string())?;
+    let id = diesel::insert_into(users::table)
+        .values((
+            users::username.eq(name),
+            users::password.eq(placeholder_hash),
+            users::email.eq(email_address),
+            users::phone.eq(format!("pragyan:{}", Uuid::new_v4())),
+            users::is_pragyan.eq(true),
+            users::is_verified.eq(true),
+        ))
+        .returning(users::id)
+        .get_result(conn)?;
+    Ok(id)
+}
+
+pub fn update_password_hash(conn: &PgConnection, user_id: i32, new_hash: &str) -> anyhow::Result<()> {
+    diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set(users::password.eq(new_hash))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn reset_password(conn: &PgConnection, phone_number: &str, password_hash: &str) -> anyhow::Result<()> {
+    diesel::update(users::table.filter(users::phone.eq(phone_number)))
+        .set(users::password.eq(password_hash))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn reset_password_for_user(
+    conn: &PgConnection,
+    user_id: i32,
+    password_hash: &str,
+) -> anyhow::Result<()> {
+    diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set(users::password.eq(password_hash))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Looks up a verified OIDC identity by email, creating a local account on
+/// first login. Like Pragyan users, OAuth users never authenticate with a
+/// password, so the stored hash is an unusable Argon2id hash of random bytes.
+pub fn get_oauth_user(conn: &PgConnection, email_address: &str, name: &str) -> anyhow::Result<i32> {
+    if let Some(user) = get_user_by_email(conn, email_address)? {
+        return Ok(user.id);
+    }
+    let placeholder_hash = password::hash(&Uuid::new_v4().to_string())?;
+    let id = diesel::insert_into(users::table)
+        .values((
+            users::username.eq(name),
+            users::password.eq(placeholder_hash),
+            users::email.eq(email_address),
+            users::phone.eq(format!("oauth:{}", Uuid::new_v4())),
+            users::is_pragyan.eq(false),
+            users::is_verified.eq(true),
+        ))
+        .returning(users::id)
+        .get_result(conn)?;
+    Ok(id)
+}
+
+pub fn verify_user(conn: &PgConnection, user_id: i32) -> anyhow::Result<()> {
+    diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set(users::is_verified.eq(true))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn set_otp_session_id(conn: &PgConnection, user_id: i32, session_id: &str) -> anyhow::Result<()> {
+    diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set(users::otp_session_id.eq(session_id))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn get_otp_session_id(conn: &PgConnection, user_id: i32) -> anyhow::Result<String> {
+    users::table
+        .filter(users::id.eq(user_id))
+        .select(users::otp_session_id)
+        .first::<Option<String>>(conn)?
+        .ok_or_else(|| anyhow::anyhow!("No OTP verification in progress for this user"))
+}
+
+pub fn user_has_totp(conn: &PgConnection, user_id: i32) -> anyhow::Result<bool> {
+    let count: i64 = user_totp::table
+        .filter(user_totp::user_id.eq(user_id))
+        .count()
+        .get_result(conn)?;
+    Ok(count > 0)
+}
+
+pub fn set_totp_secret(conn: &PgConnection, user_id: i32, secret_base32: &str) -> anyhow::Result<()> {
+    diesel::insert_into(user_totp::table)
+        .values(UserTotp {
+            user_id,
+            secret: secret_base32.to_string(),
+            created_at: Utc::now().naive_utc(),
+        })
+        .on_conflict(user_totp::user_id)
+        .do_update()
+        .set(user_totp::secret.eq(secret_base32))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn get_totp_secret(conn: &PgConnection, user_id: i32) -> anyhow::Result<Option<String>> {
+    Ok(user_totp::table
+        .filter(user_totp::user_id.eq(user_id))
+        .select(user_totp::secret)
+        .first(conn)
+        .optional()?)
+}
+
+pub fn insert_user_session(
+    conn: &PgConnection,
+    session_id: &str,
+    user_id: i32,
+    ip: &str,
+    device: &str,
+) -> anyhow::Result<()> {
+    let now = Utc::now().naive_utc();
+    diesel::insert_into(user_session::table)
+        .values(UserSession {
+            id: session_id.to_string(),
+            user_id,
+            ip: ip.to_string(),
+            device: device.to_string(),
+            created_at: now,
+            last_seen: now,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn touch_user_session(conn: &PgConnection, session_id: &str) -> anyhow::Result<()> {
+    diesel::update(user_session::table.filter(user_session::id.eq(session_id)))
+        .set(user_session::last_seen.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn get_user_sessions(conn: &PgConnection, user_id: i32) -> anyhow::Result<Vec<UserSession>> {
+    Ok(user_session::table
+        .filter(user_session::user_id.eq(user_id))
+        .order(user_session::last_seen.desc())
+        .load(conn)?)
+}
+
+pub fn delete_user_session_for_user(
+    conn: &PgConnection,
+    session_id: &str,
+    user_id: i32,
+) -> anyhow::Result<bool> {
+    let deleted = diesel::delete(
+        user_session::table
+            .filter(user_session::id.eq(session_id))
+            .filter(user_session::user_id.eq(user_id)),
+    )
+    .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+pub fn delete_other_user_sessions(
+    conn: &PgConnection,
+    user_id: i32,
+    current_session_id: &str,
+) -> anyhow::Result<usize> {
+    Ok(diesel::delete(
+        user_session::table
+            .filter(user_session::user_id.eq(user_id))
+            .filter(user_session::id.ne(current_session_id)),
+    )
+    .execute(conn)?)
+}
+
+pub fn delete_user_session(conn: &PgConnection, session_id: &str) -> anyhow::Result<()> {
+    diesel::delete(user_session::table.filter(user_session::id.eq(session_id))).execute(conn)?;
+    Ok(())
+}
+
+pub fn user_session_exists(conn: &PgConnection, session_id: &str) -> anyhow::Result<bool> {
+    let count: i64 = user_session::table
+        .filter(user_session::id.eq(session_id))
+        .count()
+        .get_result(conn)?;
+    Ok(count > 0)
+}
+
+pub fn set_email_verification_token(
+    conn: &PgConnection,
+    user_id: i32,
+    token: &str,
+    expires_at: NaiveDateTime,
+) -> anyhow::Result<()> {
+    diesel::insert_into(email_verification_token::table)
+        .values(NewEmailVerificationToken {
+            token: token.to_string(),
+            user_id,
+            expires_at,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn get_email_verification(
+    conn: &PgConnection,
+    token: &str,
+) -> anyhow::Result<Option<(i32, NaiveDateTime)>> {
+    Ok(email_verification_token::table
+        .filter(email_verification_token::token.eq(token))
+        .select((email_verification_token::user_id, email_verification_token::expires_at))
+        .first(conn)
+        .optional()?)
+}
+
+pub fn consume_email_verification(conn: &PgConnection, token: &str) -> anyhow::Result<()> {
+    diesel::delete(email_verification_token::table.filter(email_verification_token::token.eq(token)))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn set_email_reset_token(
+    conn: &PgConnection,
+    user_id: i32,
+    token: &str,
+    expires_at: NaiveDateTime,
+) -> anyhow::Result<()> {
+    diesel::insert_into(email_reset_token::table)
+        .values(NewEmailResetToken {
+            token: token.to_string(),
+            user_id,
+            expires_at,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn get_email_reset_token(
+    conn: &PgConnection,
+    token: &str,
+) -> anyhow::Result<Option<(i32, NaiveDateTime)>> {
+    Ok(email_reset_token::table
+        .filter(email_reset_token::token.eq(token))
+        .select((email_reset_token::user_id, email_reset_token::expires_at))
+        .first(conn)
+        .optional()?)
+}
+
+pub fn consume_email_reset_token(conn: &PgConnection, token: &str) -> anyhow::Result<()> {
+    diesel::delete(email_reset_token::table.filter(email_reset_token::token.eq(token))).execute(conn)?;
+    Ok(())
+}